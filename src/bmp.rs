@@ -3,6 +3,7 @@
 
 use std::io::Write;
 
+use crate::encoder::ImageEncoder;
 use crate::image::Image;
 
 const PIXELS_PER_METER_72_DPI: i32 = 2835;
@@ -37,18 +38,50 @@ impl Header {
         }
     }
 
-    /// Writes the header to the writer in packed form.
+    /// Generates the appropriate file header for an image using the larger
+    /// [`InfoHeaderV4`].
+    fn for_info_header_v4(info: &InfoHeaderV4) -> Self {
+        let total_header_size = Self::FILE_SIZE + InfoHeaderV4::FILE_SIZE;
+        Self {
+            magic: [0x42, 0x4D],
+            file_size: info.image_size + total_header_size,
+            reserved0: 0,
+            reserved1: 0,
+            image_offset: total_header_size,
+        }
+    }
+
+    /// Writes the header to the writer in its little-endian wire format.
     fn write_to_buffer(&self, writer: &mut dyn std::io::Write) -> Result<(), std::io::Error> {
-        writer.write(&self.magic)?;
-        writer.write(as_u8_slice(&self.file_size))?;
-        writer.write(as_u8_slice(&self.reserved0))?;
-        writer.write(as_u8_slice(&self.reserved1))?;
-        writer.write(as_u8_slice(&self.image_offset))?;
+        writer.write_all(&self.magic)?;
+        writer.write_all(&self.file_size.to_le_bytes())?;
+        writer.write_all(&self.reserved0.to_le_bytes())?;
+        writer.write_all(&self.reserved1.to_le_bytes())?;
+        writer.write_all(&self.image_offset.to_le_bytes())?;
         Ok(())
     }
+
+    /// Parses a [`Header`] from the first [`Self::FILE_SIZE`] bytes of `bytes`.
+    fn read_from_buffer(bytes: &[u8]) -> Result<Self, BmpError> {
+        let bytes = bytes
+            .get(..Self::FILE_SIZE as usize)
+            .ok_or(BmpError::UnexpectedEof)?;
+
+        let magic = [bytes[0], bytes[1]];
+        if magic != [0x42, 0x4D] {
+            return Err(BmpError::InvalidMagic);
+        }
+
+        Ok(Self {
+            magic,
+            file_size: u32::from_le_bytes(bytes[2..6].try_into().unwrap()),
+            reserved0: u16::from_le_bytes(bytes[6..8].try_into().unwrap()),
+            reserved1: u16::from_le_bytes(bytes[8..10].try_into().unwrap()),
+            image_offset: u32::from_le_bytes(bytes[10..14].try_into().unwrap()),
+        })
+    }
 }
 
-#[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct InfoHeader {
     /// The size of this header in bytes, without padding.
@@ -81,10 +114,16 @@ struct InfoHeader {
 }
 
 impl InfoHeader {
-    const FILE_SIZE: u32 = std::mem::size_of::<Self>() as u32;
+    /// The on-disk size of this header, i.e. the number of bytes
+    /// [`Self::write_to_buffer`] emits. Kept as a literal rather than
+    /// `size_of::<Self>()`, since this struct isn't `repr(C)` and its wire
+    /// layout is defined by the serializer, not by Rust's field layout.
+    const FILE_SIZE: u32 = 40;
 
     pub fn new(image: &Image) -> Self {
-        let image_size = (image.width + (image.width % 4) as u32) * image.height * image.bytes_per_pixel() as u32;
+        let row_bytes = image.width * image.bytes_per_pixel() as u32;
+        let padded_row_bytes = row_bytes + (4 - row_bytes % 4) % 4;
+        let image_size = padded_row_bytes * image.height;
         Self {
             header_size: InfoHeader::FILE_SIZE,
             image_width: image.width.try_into().unwrap(),
@@ -99,21 +138,255 @@ impl InfoHeader {
             num_important_colors: 0,
         }
     }
+
+    /// Builds the info header for an 8-bit indexed BMP with `palette_size`
+    /// colors, uncompressed, with rows padded to a 4-byte boundary.
+    fn new_indexed(width: u32, height: u32, palette_size: u32) -> Self {
+        let row_bytes = width + (4 - width % 4) % 4;
+        Self {
+            header_size: InfoHeader::FILE_SIZE,
+            image_width: width.try_into().unwrap(),
+            image_height: height.try_into().unwrap(),
+            color_planes: 1,
+            bits_per_pixel: 8,
+            compression_method: CompressionMethod::BI_RGB,
+            image_size: row_bytes * height,
+            resolution_x: PIXELS_PER_METER_72_DPI,
+            resolution_y: PIXELS_PER_METER_72_DPI,
+            palette_size,
+            num_important_colors: 0,
+        }
+    }
+
+    /// Parses an [`InfoHeader`] from the first [`Self::FILE_SIZE`] bytes of `bytes`.
+    fn read_from_buffer(bytes: &[u8]) -> Result<Self, BmpError> {
+        let bytes = bytes
+            .get(..Self::FILE_SIZE as usize)
+            .ok_or(BmpError::UnexpectedEof)?;
+
+        let header_size = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let compression_method = u32::from_le_bytes(bytes[16..20].try_into().unwrap());
+
+        Ok(Self {
+            header_size,
+            image_width: i32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            image_height: i32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            color_planes: u16::from_le_bytes(bytes[12..14].try_into().unwrap()),
+            bits_per_pixel: u16::from_le_bytes(bytes[14..16].try_into().unwrap()),
+            compression_method: CompressionMethod::from_u32(compression_method)
+                .ok_or(BmpError::UnsupportedCompression(compression_method))?,
+            image_size: u32::from_le_bytes(bytes[20..24].try_into().unwrap()),
+            resolution_x: i32::from_le_bytes(bytes[24..28].try_into().unwrap()),
+            resolution_y: i32::from_le_bytes(bytes[28..32].try_into().unwrap()),
+            palette_size: u32::from_le_bytes(bytes[32..36].try_into().unwrap()),
+            num_important_colors: u32::from_le_bytes(bytes[36..40].try_into().unwrap()),
+        })
+    }
+
+    /// Writes the info header to the writer in its little-endian wire format.
+    fn write_to_buffer(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(&self.header_size.to_le_bytes())?;
+        writer.write_all(&self.image_width.to_le_bytes())?;
+        writer.write_all(&self.image_height.to_le_bytes())?;
+        writer.write_all(&self.color_planes.to_le_bytes())?;
+        writer.write_all(&self.bits_per_pixel.to_le_bytes())?;
+        writer.write_all(&(self.compression_method as u32).to_le_bytes())?;
+        writer.write_all(&self.image_size.to_le_bytes())?;
+        writer.write_all(&self.resolution_x.to_le_bytes())?;
+        writer.write_all(&self.resolution_y.to_le_bytes())?;
+        writer.write_all(&self.palette_size.to_le_bytes())?;
+        writer.write_all(&self.num_important_colors.to_le_bytes())?;
+        Ok(())
+    }
 }
 
 #[repr(u32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 enum CompressionMethod {
     /// No compression
     BI_RGB = 0,
+    /// 8-bit run-length encoding, for indexed images.
+    BI_RLE8 = 1,
+    /// 4-bit run-length encoding, for indexed images.
+    BI_RLE4 = 2,
+    /// Pixel data laid out according to explicit channel bitmasks, as used by
+    /// [`InfoHeaderV4`] for 32-bit BGRA output.
+    BI_BITFIELDS = 3,
 }
 
-fn as_u8_slice<T: Sized>(o: &T) -> &[u8] {
-    unsafe { std::slice::from_raw_parts((o as *const T).cast(), std::mem::size_of::<T>()) }
+impl CompressionMethod {
+    fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(Self::BI_RGB),
+            1 => Some(Self::BI_RLE8),
+            2 => Some(Self::BI_RLE4),
+            3 => Some(Self::BI_BITFIELDS),
+            _ => None,
+        }
+    }
+}
+
+/// The `BITMAPV4HEADER` info header, an extension of [`InfoHeader`] that adds
+/// explicit channel bitmasks and a color space, used here for 32-bit BGRA
+/// output so that the alpha channel survives encoding.
+#[derive(Clone, Copy, Debug)]
+struct InfoHeaderV4 {
+    header_size: u32,
+    image_width: i32,
+    image_height: i32,
+    color_planes: u16,
+    bits_per_pixel: u16,
+    compression_method: CompressionMethod,
+    image_size: u32,
+    resolution_x: i32,
+    resolution_y: i32,
+    palette_size: u32,
+    num_important_colors: u32,
+    /// Bitmask selecting the red channel's bits within each pixel.
+    red_mask: u32,
+    /// Bitmask selecting the green channel's bits within each pixel.
+    green_mask: u32,
+    /// Bitmask selecting the blue channel's bits within each pixel.
+    blue_mask: u32,
+    /// Bitmask selecting the alpha channel's bits within each pixel.
+    alpha_mask: u32,
+    /// `LCS_SRGB`, since the channel values are plain sRGB bytes.
+    color_space_type: u32,
+    /// Unused outside of `LCS_CALIBRATED_RGB`; zeroed for `LCS_SRGB`.
+    color_space_endpoints: [u32; 9],
+    /// Unused outside of `LCS_CALIBRATED_RGB`; zeroed for `LCS_SRGB`.
+    gamma_red: u32,
+    gamma_green: u32,
+    gamma_blue: u32,
+}
+
+impl InfoHeaderV4 {
+    /// The on-disk size of this header, i.e. the number of bytes
+    /// [`Self::write_to_buffer`] emits. Kept as a literal rather than
+    /// `size_of::<Self>()`, since this struct isn't `repr(C)` and its wire
+    /// layout is defined by the serializer, not by Rust's field layout.
+    const FILE_SIZE: u32 = 108;
+
+    /// `LCS_SRGB`, the `'sRGB'` FourCC.
+    const LCS_SRGB: u32 = 0x7352_4742;
+
+    fn new(image: &Image) -> Self {
+        Self {
+            header_size: Self::FILE_SIZE,
+            image_width: image.width.try_into().unwrap(),
+            image_height: image.height.try_into().unwrap(),
+            color_planes: 1,
+            bits_per_pixel: 32,
+            compression_method: CompressionMethod::BI_BITFIELDS,
+            image_size: image.width * image.height * 4,
+            resolution_x: PIXELS_PER_METER_72_DPI,
+            resolution_y: PIXELS_PER_METER_72_DPI,
+            palette_size: 0,
+            num_important_colors: 0,
+            red_mask: 0x00FF0000,
+            green_mask: 0x0000FF00,
+            blue_mask: 0x0000_00FF,
+            alpha_mask: 0xFF00_0000,
+            color_space_type: Self::LCS_SRGB,
+            color_space_endpoints: [0; 9],
+            gamma_red: 0,
+            gamma_green: 0,
+            gamma_blue: 0,
+        }
+    }
+
+    /// Writes the info header to the writer in its little-endian wire format.
+    fn write_to_buffer(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(&self.header_size.to_le_bytes())?;
+        writer.write_all(&self.image_width.to_le_bytes())?;
+        writer.write_all(&self.image_height.to_le_bytes())?;
+        writer.write_all(&self.color_planes.to_le_bytes())?;
+        writer.write_all(&self.bits_per_pixel.to_le_bytes())?;
+        writer.write_all(&(self.compression_method as u32).to_le_bytes())?;
+        writer.write_all(&self.image_size.to_le_bytes())?;
+        writer.write_all(&self.resolution_x.to_le_bytes())?;
+        writer.write_all(&self.resolution_y.to_le_bytes())?;
+        writer.write_all(&self.palette_size.to_le_bytes())?;
+        writer.write_all(&self.num_important_colors.to_le_bytes())?;
+        writer.write_all(&self.red_mask.to_le_bytes())?;
+        writer.write_all(&self.green_mask.to_le_bytes())?;
+        writer.write_all(&self.blue_mask.to_le_bytes())?;
+        writer.write_all(&self.alpha_mask.to_le_bytes())?;
+        writer.write_all(&self.color_space_type.to_le_bytes())?;
+        for endpoint in self.color_space_endpoints {
+            writer.write_all(&endpoint.to_le_bytes())?;
+        }
+        writer.write_all(&self.gamma_red.to_le_bytes())?;
+        writer.write_all(&self.gamma_green.to_le_bytes())?;
+        writer.write_all(&self.gamma_blue.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+/// Errors that can occur while decoding a BMP file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BmpError {
+    /// The file did not start with the `BM` signature.
+    InvalidMagic,
+    /// The header's `file_size` field did not match the number of bytes given.
+    InvalidFileSize { expected: u32, actual: usize },
+    /// The header's `compression_method` is not one this decoder understands.
+    UnsupportedCompression(u32),
+    /// The header's `bits_per_pixel` is not one this decoder understands.
+    UnsupportedBitDepth(u16),
+    /// The image dimensions are non-positive, or would overflow `usize` when
+    /// computing the size of the pixel buffer.
+    FormatError,
+    /// The input ended before a complete header or pixel array could be read.
+    UnexpectedEof,
+}
+
+impl std::fmt::Display for BmpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "missing or invalid 'BM' signature"),
+            Self::InvalidFileSize { expected, actual } => {
+                write!(f, "file_size header field is {expected}, but the input is {actual} bytes")
+            }
+            Self::UnsupportedCompression(method) => write!(f, "unsupported compression method {method}"),
+            Self::UnsupportedBitDepth(bits) => write!(f, "unsupported bit depth {bits}"),
+            Self::FormatError => write!(f, "image dimensions are invalid or too large"),
+            Self::UnexpectedEof => write!(f, "unexpected end of input"),
+        }
+    }
+}
+
+impl std::error::Error for BmpError {}
+
+/// Computes `width * height * channels` as a [`usize`], returning [`None`]
+/// instead of overflowing or wrapping if either dimension is non-positive or
+/// the product does not fit in a `usize`.
+fn num_bytes(width: i32, height: i32, channels: usize) -> Option<usize> {
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+
+    (width as usize).checked_mul(height as usize)?.checked_mul(channels)
+}
+
+/// Encodes images as BMP. See [`encode`] for the format details.
+pub struct BmpEncoder;
+
+impl ImageEncoder for BmpEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        encode(image, &mut buffer);
+        out.write_all(&buffer)
+    }
 }
 
 pub fn encode(image: &Image, buffer: &mut Vec<u8>) {
+    if let Some(alpha) = image.alpha.as_deref() {
+        encode_v4(image, alpha, buffer);
+        return;
+    }
+
     let mut pixels = image.buffer.clone();
     let pixel_data = {
         let colors = pixels.as_mut_slice();
@@ -138,19 +411,370 @@ pub fn encode(image: &Image, buffer: &mut Vec<u8>) {
     let header = Header::for_info_header(&info_header);
 
     header.write_to_buffer(buffer).unwrap();
-    buffer.write(as_u8_slice(&info_header)).unwrap();
+    info_header.write_to_buffer(buffer).unwrap();
 
     let bytes_per_line = image.width as usize * image.bytes_per_pixel() as usize;
     let mut y_offset = 0;
     while y_offset < pixel_data.len() {
         buffer
-            .write(unsafe {
+            .write_all(unsafe {
                 std::slice::from_raw_parts(pixel_data.as_ptr().add(y_offset).cast(), bytes_per_line)
             })
             .unwrap();
-        buffer.write(&align_bytes[0..align]).unwrap();
-        y_offset += image.width as usize + align;
+        buffer.write_all(&align_bytes[0..align]).unwrap();
+        y_offset += image.width as usize;
+    }
+}
+
+/// Encodes an image with an alpha channel as a 32-bit BGRA BMP, using a
+/// `BITMAPV4HEADER` so viewers that honor its bitfields preserve
+/// transparency. 32 bits per pixel is already a multiple of 4 bytes, so rows
+/// never need padding.
+fn encode_v4(image: &Image, alpha: &[u8], buffer: &mut Vec<u8>) {
+    assert_eq!(alpha.len(), image.buffer.len());
+
+    let info_header = InfoHeaderV4::new(image);
+    let header = Header::for_info_header_v4(&info_header);
+
+    header.write_to_buffer(buffer).unwrap();
+    info_header.write_to_buffer(buffer).unwrap();
+
+    for (pixel, &a) in image.buffer.iter().zip(alpha) {
+        buffer.write_all(&[pixel[2], pixel[1], pixel[0], a]).unwrap();
+    }
+}
+
+/// Encodes a palettized image as an indexed BMP, compressing the pixel data
+/// with `BI_RLE8` (`bits_per_pixel == 8`) or `BI_RLE4` (`bits_per_pixel == 4`).
+///
+/// `indices` is a row-major buffer of one palette index per pixel, and
+/// `palette` holds the corresponding RGB colors (at most 256 of them).
+pub fn encode_indexed(width: u32, height: u32, bits_per_pixel: u8, palette: &[[u8; 3]], indices: &[u8], buffer: &mut Vec<u8>) {
+    assert!(bits_per_pixel == 4 || bits_per_pixel == 8);
+    assert_eq!(indices.len(), width as usize * height as usize);
+
+    let compressed = if bits_per_pixel == 8 {
+        rle8_encode(indices, width as usize, height as usize)
+    } else {
+        rle4_encode(indices, width as usize, height as usize)
+    };
+
+    let info_header = InfoHeader {
+        header_size: InfoHeader::FILE_SIZE,
+        image_width: width.try_into().unwrap(),
+        image_height: height.try_into().unwrap(),
+        color_planes: 1,
+        bits_per_pixel: bits_per_pixel.into(),
+        compression_method: if bits_per_pixel == 8 { CompressionMethod::BI_RLE8 } else { CompressionMethod::BI_RLE4 },
+        image_size: compressed.len() as u32,
+        resolution_x: PIXELS_PER_METER_72_DPI,
+        resolution_y: PIXELS_PER_METER_72_DPI,
+        palette_size: palette.len() as u32,
+        num_important_colors: 0,
+    };
+
+    let palette_bytes = palette.len() as u32 * 4;
+    let mut header = Header::for_info_header(&info_header);
+    header.file_size += palette_bytes;
+    header.image_offset += palette_bytes;
+
+    header.write_to_buffer(buffer).unwrap();
+    info_header.write_to_buffer(buffer).unwrap();
+
+    for color in palette {
+        buffer.write_all(&[color[2], color[1], color[0], 0]).unwrap();
     }
+
+    buffer.write_all(&compressed).unwrap();
+}
+
+/// Encodes an image as an uncompressed 8-bit indexed BMP, reducing its
+/// colors to at most 256 with [`median_cut`] quantization and mapping each
+/// pixel to its nearest palette entry. This can dramatically shrink renders
+/// with a limited color count, at the cost of some quantization error.
+pub fn encode_quantized(image: &Image, buffer: &mut Vec<u8>) {
+    // median_cut's single starting box would divide its color sum by zero
+    // colors when averaged, so a 0-pixel image gets an empty palette instead
+    // of being quantized.
+    let palette = if image.buffer.is_empty() { Vec::new() } else { median_cut(&image.buffer, 256) };
+    let indices: Vec<u8> = image.buffer.iter().map(|&color| nearest_palette_index(color, &palette)).collect();
+
+    let info_header = InfoHeader::new_indexed(image.width, image.height, palette.len() as u32);
+    let palette_bytes = palette.len() as u32 * 4;
+
+    let mut header = Header::for_info_header(&info_header);
+    header.file_size += palette_bytes;
+    header.image_offset += palette_bytes;
+
+    header.write_to_buffer(buffer).unwrap();
+    info_header.write_to_buffer(buffer).unwrap();
+
+    for color in &palette {
+        buffer.write_all(&[color[2], color[1], color[0], 0]).unwrap();
+    }
+
+    let align_bytes: [u8; 4] = [0, 0, 0, 0];
+    let align = (4 - image.width as usize % 4) % 4;
+    for row in indices.chunks(image.width as usize) {
+        buffer.write_all(row).unwrap();
+        buffer.write_all(&align_bytes[0..align]).unwrap();
+    }
+}
+
+/// A box of colors considered for splitting during median-cut quantization.
+struct ColorBox {
+    colors: Vec<[u8; 3]>,
+}
+
+impl ColorBox {
+    /// The min-max spread of `channel` (0 = red, 1 = green, 2 = blue) across
+    /// this box's colors.
+    fn channel_range(&self, channel: usize) -> u8 {
+        let (min, max) = self
+            .colors
+            .iter()
+            .map(|c| c[channel])
+            .fold((u8::MAX, u8::MIN), |(min, max), v| (min.min(v), max.max(v)));
+        max - min
+    }
+
+    /// The channel with the widest min-max spread, the one median-cut splits
+    /// along.
+    fn widest_channel(&self) -> usize {
+        (0..3).max_by_key(|&channel| self.channel_range(channel)).unwrap()
+    }
+
+    /// The average color of this box, used as its palette entry.
+    fn average(&self) -> [u8; 3] {
+        let sums = self.colors.iter().fold([0u32; 3], |mut sums, c| {
+            for channel in 0..3 {
+                sums[channel] += c[channel] as u32;
+            }
+            sums
+        });
+        let n = self.colors.len() as u32;
+        std::array::from_fn(|channel| (sums[channel] / n) as u8)
+    }
+
+    /// Splits this box in two at the median of its widest channel.
+    fn split(mut self) -> (Self, Self) {
+        let channel = self.widest_channel();
+        self.colors.sort_unstable_by_key(|c| c[channel]);
+        let second_half = self.colors.split_off(self.colors.len() / 2);
+        (self, Self { colors: second_half })
+    }
+}
+
+/// Reduces `colors` to at most `max_colors` representative colors using
+/// median-cut quantization: repeatedly take the box whose widest channel has
+/// the largest range, sort its colors along that channel, and split at the
+/// median, until there are `max_colors` boxes or none left large enough to
+/// split. Each box's palette entry is the average of its colors.
+fn median_cut(colors: &[[u8; 3]], max_colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![ColorBox { colors: colors.to_vec() }];
+
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.colors.len() > 1 && b.channel_range(b.widest_channel()) > 0)
+            .max_by_key(|(_, b)| b.channel_range(b.widest_channel()));
+
+        let Some((index, _)) = widest else {
+            break;
+        };
+
+        let (a, b) = boxes.remove(index).split();
+        boxes.push(a);
+        boxes.push(b);
+    }
+
+    boxes.iter().map(ColorBox::average).collect()
+}
+
+/// Finds the palette entry closest to `color` by squared Euclidean distance.
+fn nearest_palette_index(color: [u8; 3], palette: &[[u8; 3]]) -> u8 {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| {
+            let d = std::array::from_fn::<i32, 3, _>(|i| color[i] as i32 - p[i] as i32);
+            d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+        })
+        .map(|(index, _)| index as u8)
+        .unwrap()
+}
+
+/// Compresses a row-major buffer of 8-bit palette indices using `BI_RLE8`.
+///
+/// Runs of two or more repeated indices are emitted as `(count, value)`
+/// pairs; everything else is accumulated into literal runs and emitted in
+/// absolute mode (`0, n` followed by `n` index bytes, padded to a 16-bit
+/// boundary), except for runs shorter than 3 indices, which are cheaper to
+/// emit as a sequence of 1-counts. Each row but the last ends with the
+/// end-of-line escape (`0, 0`); the last row ends with end-of-bitmap (`0, 1`)
+/// in its place.
+/// Delta escapes (`0, 2, dx, dy`) are never emitted.
+fn rle8_encode(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for row in indices.chunks(width).take(height) {
+        encode_rle_row(row, &mut out, |out, value| out.push(value), |literal| literal.to_vec());
+        out.push(0);
+        out.push(0);
+    }
+
+    out.truncate(out.len() - 2);
+    out.push(0);
+    out.push(1);
+    out
+}
+
+/// Compresses a row-major buffer of 4-bit palette indices using `BI_RLE4`.
+///
+/// Identical to [`rle8_encode`], except each emitted byte packs two 4-bit
+/// indices, high nibble first.
+fn rle4_encode(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+
+    for row in indices.chunks(width).take(height) {
+        encode_rle_row(
+            row,
+            &mut out,
+            |out, value| out.push((value << 4) | value),
+            |literal| {
+                literal
+                    .chunks(2)
+                    .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+                    .collect()
+            },
+        );
+        out.push(0);
+        out.push(0);
+    }
+
+    out.truncate(out.len() - 2);
+    out.push(0);
+    out.push(1);
+    out
+}
+
+/// Shared greedy run/literal split used by [`rle8_encode`] and
+/// [`rle4_encode`]; `pack_single` and `pack_literal` account for the
+/// difference in how a lone run value or a literal block get packed into
+/// bytes.
+fn encode_rle_row(row: &[u8], out: &mut Vec<u8>, pack_single: impl Fn(&mut Vec<u8>, u8), pack_literal: impl Fn(&[u8]) -> Vec<u8>) {
+    let mut i = 0;
+    while i < row.len() {
+        let run_len = row[i..].iter().take_while(|&&v| v == row[i]).count();
+
+        if run_len >= 2 {
+            let mut remaining = run_len;
+            while remaining > 0 {
+                let chunk = remaining.min(255);
+                out.push(chunk as u8);
+                pack_single(out, row[i]);
+                remaining -= chunk;
+            }
+            i += run_len;
+            continue;
+        }
+
+        let start = i;
+        while i < row.len() {
+            let next_run = row[i..].iter().take_while(|&&v| v == row[i]).count();
+            if next_run >= 2 {
+                break;
+            }
+            i += 1;
+        }
+
+        let literal = &row[start..i];
+        if literal.len() < 3 {
+            for &value in literal {
+                out.push(1);
+                pack_single(out, value);
+            }
+        } else {
+            let mut remaining = literal;
+            while !remaining.is_empty() {
+                let mut take = remaining.len().min(255);
+                // A trailing block of 1 or 2 indices would be emitted as
+                // `0, 1` or `0, 2`, which decoders read as the end-of-bitmap
+                // and delta escapes rather than an absolute run. Shave a few
+                // indices off this block so the remainder is 0 or >= 3.
+                let left_over = remaining.len() - take;
+                if left_over > 0 && left_over < 3 {
+                    take -= 3 - left_over;
+                }
+
+                let (chunk, rest) = remaining.split_at(take);
+                out.push(0);
+                out.push(chunk.len() as u8);
+                let packed = pack_literal(chunk);
+                out.extend_from_slice(&packed);
+                if packed.len() % 2 != 0 {
+                    out.push(0);
+                }
+                remaining = rest;
+            }
+        }
+    }
+}
+
+/// Decodes an uncompressed 24-bit BMP into an [`Image`].
+///
+/// Rows are stored bottom-up in the file by default, matching [`Image::buffer`]'s
+/// own bottom-up row order, so the common case needs no reshuffling; a
+/// negative `image_height` marks a top-down file, whose rows are reversed on
+/// the way in so the result still lines up with [`encode`]'s output.
+pub fn decode(bytes: &[u8]) -> Result<Image, BmpError> {
+    let header = Header::read_from_buffer(bytes)?;
+
+    if header.file_size as usize != bytes.len() {
+        return Err(BmpError::InvalidFileSize {
+            expected: header.file_size,
+            actual: bytes.len(),
+        });
+    }
+
+    let info = InfoHeader::read_from_buffer(&bytes[Header::FILE_SIZE as usize..])?;
+
+    if info.bits_per_pixel != 24 {
+        return Err(BmpError::UnsupportedBitDepth(info.bits_per_pixel));
+    }
+
+    let channels = (info.bits_per_pixel / 8) as usize;
+    let top_down = info.image_height < 0;
+    let height = info.image_height.unsigned_abs() as i32;
+
+    num_bytes(info.image_width, height, channels).ok_or(BmpError::FormatError)?;
+    let width = info.image_width as usize;
+    let height = height as usize;
+
+    let row_bytes = width * channels;
+    let padding = (4 - row_bytes % 4) % 4;
+    let total_bytes = (row_bytes + padding).checked_mul(height).ok_or(BmpError::FormatError)?;
+    let pixel_data = bytes
+        .get(header.image_offset as usize..)
+        .filter(|data| data.len() >= total_bytes)
+        .ok_or(BmpError::UnexpectedEof)?;
+
+    let mut image = Image::new(width as u32, height as u32);
+    let mut output = vec![[0u8; 3]; width * height];
+
+    for row in 0..height {
+        let dest_row = if top_down { height - 1 - row } else { row };
+        let row_start = row * (row_bytes + padding);
+        let row_data = &pixel_data[row_start..row_start + row_bytes];
+
+        for (x, bgr) in row_data.chunks_exact(channels).enumerate() {
+            output[dest_row * width + x] = [bgr[2], bgr[1], bgr[0]];
+        }
+    }
+
+    image.buffer = output;
+    Ok(image)
 }
 
 #[cfg(test)]
@@ -161,5 +785,95 @@ mod tests {
     fn header_sizes() {
         assert_eq!(Header::FILE_SIZE, 14);
         assert_eq!(InfoHeader::FILE_SIZE, 40);
+        assert_eq!(InfoHeaderV4::FILE_SIZE, 108);
+    }
+
+    #[test]
+    fn encode_picks_v4_header_when_image_has_alpha() {
+        let mut image = Image::new(2, 2);
+        image.alpha = Some(vec![10, 20, 30, 40]);
+
+        let mut encoded = Vec::new();
+        encode(&image, &mut encoded);
+
+        // 14-byte Header + 108-byte InfoHeaderV4 + 2x2 BGRA pixels.
+        assert_eq!(encoded.len(), 14 + 108 + 2 * 2 * 4);
+        assert_eq!(encoded[14..18], InfoHeaderV4::FILE_SIZE.to_le_bytes());
+        assert_eq!(encoded[30..34], 3u32.to_le_bytes()); // BI_BITFIELDS
+    }
+
+    #[test]
+    fn num_bytes_rejects_non_positive_dimensions() {
+        assert_eq!(num_bytes(0, 4, 3), None);
+        assert_eq!(num_bytes(4, 0, 3), None);
+        assert_eq!(num_bytes(-1, 4, 3), None);
+    }
+
+    #[test]
+    fn num_bytes_rejects_overflow() {
+        assert_eq!(num_bytes(i32::MAX, i32::MAX, usize::MAX), None);
+        assert_eq!(num_bytes(4, 4, 3), Some(48));
+    }
+
+    #[test]
+    fn rle8_encodes_runs_and_literals() {
+        // A run of 4, then a literal block of 4 distinct values, then a run of 2.
+        let row = [5, 5, 5, 5, 1, 2, 3, 4, 9, 9];
+        let encoded = rle8_encode(&row, row.len(), 1);
+
+        assert_eq!(
+            encoded,
+            vec![
+                4, 5, // run of four 5s
+                0, 4, 1, 2, 3, 4, // absolute mode: 4 literal bytes
+                2, 9, // run of two 9s
+                0, 1, // end of bitmap (the single row's end-of-line is elided)
+            ]
+        );
+    }
+
+    #[test]
+    fn median_cut_preserves_distinct_colors_under_the_cap() {
+        let colors = vec![[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+        let palette = median_cut(&colors, 256);
+        assert_eq!(palette.len(), 3);
+    }
+
+    #[test]
+    fn median_cut_caps_palette_size() {
+        let colors: Vec<[u8; 3]> = (0..=255).map(|v| [v, v, v]).collect();
+        let palette = median_cut(&colors, 16);
+        assert_eq!(palette.len(), 16);
+    }
+
+    #[test]
+    fn encode_quantized_writes_indexed_header() {
+        let mut image = Image::new(2, 2);
+        image.buffer = vec![[255, 0, 0], [255, 0, 0], [0, 255, 0], [0, 0, 255]];
+
+        let mut encoded = Vec::new();
+        encode_quantized(&image, &mut encoded);
+
+        let header = Header::read_from_buffer(&encoded).unwrap();
+        let info = InfoHeader::read_from_buffer(&encoded[Header::FILE_SIZE as usize..]).unwrap();
+        assert_eq!(info.bits_per_pixel, 8);
+        assert_eq!(info.palette_size, 3);
+        assert_eq!(header.image_offset, Header::FILE_SIZE + InfoHeader::FILE_SIZE + 3 * 4);
+    }
+
+    #[test]
+    fn round_trips_through_encode_and_decode() {
+        let mut image = Image::new(5, 3);
+        for (i, pixel) in image.buffer.iter_mut().enumerate() {
+            *pixel = [(i * 7) as u8, (i * 13) as u8, (i * 31) as u8];
+        }
+
+        let mut encoded = Vec::new();
+        encode(&image, &mut encoded);
+
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded.width, image.width);
+        assert_eq!(decoded.height, image.height);
+        assert_eq!(decoded.buffer, image.buffer);
     }
 }
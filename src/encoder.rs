@@ -0,0 +1,59 @@
+//! A format-agnostic interface for encoding [`Image`]s, so callers can pick
+//! an output format without touching any format's header details.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::bmp::BmpEncoder;
+use crate::image::Image;
+use crate::png::PngEncoder;
+
+/// Something that can serialize an [`Image`] into one file format.
+pub trait ImageEncoder {
+    /// Encodes `image` and writes it to `out`.
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> std::io::Result<()>;
+}
+
+/// The image file formats this crate knows how to encode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+}
+
+impl ImageFormat {
+    /// Guesses the format from a file extension (case-insensitive, without
+    /// the leading `.`), returning `None` if it isn't recognized.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "bmp" => Some(Self::Bmp),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from a file path's extension, returning `None` if
+    /// there is no extension or it isn't recognized.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    /// The encoder that implements this format.
+    pub fn encoder(self) -> Box<dyn ImageEncoder> {
+        match self {
+            Self::Bmp => Box::new(BmpEncoder),
+            Self::Png => Box::new(PngEncoder),
+        }
+    }
+}
+
+/// Encodes `image` as `format` and writes it to `out`.
+pub fn encode(image: &Image, format: ImageFormat, out: &mut dyn Write) -> std::io::Result<()> {
+    format.encoder().encode(image, out)
+}
+
+/// Encodes `image`, picking the format from `path`'s extension, and writes
+/// it to `out`. Returns `None` if the extension isn't a recognized format.
+pub fn encode_for_path(image: &Image, path: &Path, out: &mut dyn Write) -> Option<std::io::Result<()>> {
+    Some(encode(image, ImageFormat::from_path(path)?, out))
+}
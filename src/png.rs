@@ -0,0 +1,384 @@
+//! A minimal, non-interlaced PNG encoder: just enough of the format to emit
+//! an 8-bit truecolor RGB image as `IHDR` + one `IDAT` + `IEND`, with no
+//! external compression dependency.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::encoder::ImageEncoder;
+use crate::image::Image;
+
+const SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Encodes images as PNG. See [`encode`] for the format details.
+pub struct PngEncoder;
+
+impl ImageEncoder for PngEncoder {
+    fn encode(&self, image: &Image, out: &mut dyn Write) -> std::io::Result<()> {
+        out.write_all(&encode(image))
+    }
+}
+
+/// Encodes `image` as an 8-bit truecolor (color type 2), non-interlaced PNG.
+///
+/// Each scanline is prefixed with filter type `0` (None) and the whole image
+/// is written as a single `IDAT` chunk, LZ77-and-fixed-Huffman DEFLATE
+/// compressed and wrapped in a zlib stream.
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&SIGNATURE);
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&image.width.to_be_bytes());
+    ihdr.extend_from_slice(&image.height.to_be_bytes());
+    ihdr.push(8); // bit depth
+    ihdr.push(2); // color type: truecolor RGB
+    ihdr.push(0); // compression method: deflate
+    ihdr.push(0); // filter method: adaptive (only type 0 used here)
+    ihdr.push(0); // interlace method: none
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &zlib_compress(&filtered_scanlines(image)));
+    write_chunk(&mut out, b"IEND", &[]);
+
+    out
+}
+
+/// Lays out `image`'s pixels as PNG scanlines, each prefixed with filter type
+/// `0` (None). PNG rows run top-down, the opposite of [`Image::buffer`]'s
+/// bottom-up order, so rows are emitted in reverse.
+fn filtered_scanlines(image: &Image) -> Vec<u8> {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+
+    for buffer_row in (0..height).rev() {
+        raw.push(0);
+        for pixel in &image.buffer[buffer_row * width..(buffer_row + 1) * width] {
+            raw.extend_from_slice(pixel);
+        }
+    }
+
+    raw
+}
+
+/// Writes a length-prefixed, CRC-suffixed PNG chunk to `out`.
+fn write_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let mut crc_input = Vec::with_capacity(chunk_type.len() + data.len());
+    crc_input.extend_from_slice(chunk_type);
+    crc_input.extend_from_slice(data);
+
+    out.extend_from_slice(&crc_input);
+    out.extend_from_slice(&crc32(&crc_input).to_be_bytes());
+}
+
+/// Wraps `data` in a minimal zlib stream: a 2-byte header, `data` DEFLATE
+/// compressed as a single fixed-Huffman block, and a trailing Adler-32
+/// checksum.
+fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01]; // CMF, FLG: deflate, 32K window, fastest
+    out.extend(deflate_fixed_huffman(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// A single literal byte, or a back-reference to a previous run of
+/// `length` bytes starting `distance` bytes back, as produced by [`lz77`].
+enum LzToken {
+    Literal(u8),
+    Match { length: u16, distance: u16 },
+}
+
+/// The longest back-reference DEFLATE can express, and how far back it can
+/// point.
+const MAX_MATCH_LEN: usize = 258;
+const MIN_MATCH_LEN: usize = 3;
+const MAX_DISTANCE: usize = 32768;
+
+/// Greedily finds LZ77 back-references in `data`, hashing every 3-byte
+/// sequence seen so far to a handful of its most recent positions and
+/// picking whichever gives the longest match within [`MAX_DISTANCE`]. Falls
+/// back to a literal when nothing matches at least [`MIN_MATCH_LEN`] bytes.
+fn lz77(data: &[u8]) -> Vec<LzToken> {
+    /// How many previous positions of a given 3-byte key to try matching
+    /// against; bounds the search cost at the expense of match quality.
+    const MAX_CANDIDATES: usize = 32;
+
+    let mut tokens = Vec::new();
+    let mut chains: HashMap<[u8; 3], Vec<usize>> = HashMap::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let mut best = (0usize, 0usize); // (length, distance)
+
+        if i + MIN_MATCH_LEN <= data.len() {
+            let key = [data[i], data[i + 1], data[i + 2]];
+            if let Some(positions) = chains.get(&key) {
+                let max_len = (data.len() - i).min(MAX_MATCH_LEN);
+                for &start in positions.iter().rev().take(MAX_CANDIDATES) {
+                    if i - start > MAX_DISTANCE {
+                        break;
+                    }
+                    let len = (0..max_len).take_while(|&n| data[start + n] == data[i + n]).count();
+                    if len > best.0 {
+                        best = (len, i - start);
+                    }
+                }
+            }
+        }
+
+        if best.0 >= MIN_MATCH_LEN {
+            for p in i..(i + best.0).min(data.len().saturating_sub(MIN_MATCH_LEN - 1)) {
+                chains.entry([data[p], data[p + 1], data[p + 2]]).or_default().push(p);
+            }
+            tokens.push(LzToken::Match { length: best.0 as u16, distance: best.1 as u16 });
+            i += best.0;
+        } else {
+            if i + MIN_MATCH_LEN <= data.len() {
+                chains.entry([data[i], data[i + 1], data[i + 2]]).or_default().push(i);
+            }
+            tokens.push(LzToken::Literal(data[i]));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Encodes `data` as a single DEFLATE block (`BFINAL = 1`) using fixed
+/// Huffman codes (`BTYPE = 01`), the simplest compressed block type to
+/// produce without writing out a custom Huffman table. Back-references come
+/// from [`lz77`].
+fn deflate_fixed_huffman(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::new();
+    bits.write_bits(1, 1); // BFINAL
+    bits.write_bits(0b01, 2); // BTYPE: fixed Huffman
+
+    for token in lz77(data) {
+        match token {
+            LzToken::Literal(byte) => bits.write_huffman(fixed_litlen_code(byte as u16)),
+            LzToken::Match { length, distance } => {
+                let (base, symbol, extra_bits) = length_code(length);
+                bits.write_huffman(fixed_litlen_code(symbol));
+                bits.write_bits((length as u32) - base as u32, extra_bits);
+
+                let (base, symbol, extra_bits) = distance_code(distance);
+                bits.write_huffman(fixed_distance_code(symbol));
+                bits.write_bits((distance as u32) - base as u32, extra_bits);
+            }
+        }
+    }
+
+    bits.write_huffman(fixed_litlen_code(256)); // end of block
+    bits.finish()
+}
+
+/// Accumulates bits into bytes, least-significant bit first, the packing
+/// DEFLATE uses for everything except Huffman codes themselves.
+struct BitWriter {
+    bytes: Vec<u8>,
+    partial: u8,
+    filled: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), partial: 0, filled: 0 }
+    }
+
+    fn push_bit(&mut self, bit: u8) {
+        self.partial |= bit << self.filled;
+        self.filled += 1;
+        if self.filled == 8 {
+            self.bytes.push(self.partial);
+            self.partial = 0;
+            self.filled = 0;
+        }
+    }
+
+    /// Writes the low `count` bits of `value`, least-significant bit first.
+    fn write_bits(&mut self, value: u32, count: u8) {
+        for i in 0..count {
+            self.push_bit(((value >> i) & 1) as u8);
+        }
+    }
+
+    /// Writes a Huffman code, most-significant bit first — the one place in
+    /// DEFLATE that isn't packed LSB-first.
+    fn write_huffman(&mut self, (code, length): (u16, u8)) {
+        for i in (0..length).rev() {
+            self.push_bit(((code >> i) & 1) as u8);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.filled > 0 {
+            self.bytes.push(self.partial);
+        }
+        self.bytes
+    }
+}
+
+/// The fixed Huffman code (RFC 1951 §3.2.6) for a literal/length alphabet
+/// symbol: literal byte values 0-255, 256 for end-of-block, or 257-285 for a
+/// match length.
+fn fixed_litlen_code(symbol: u16) -> (u16, u8) {
+    match symbol {
+        0..=143 => (0b0011_0000 + symbol, 8),
+        144..=255 => (0b1_1001_0000 + (symbol - 144), 9),
+        256..=279 => (symbol - 256, 7),
+        280..=287 => (0b1100_0000 + (symbol - 280), 8),
+        _ => unreachable!("not a literal/length symbol: {symbol}"),
+    }
+}
+
+/// The fixed Huffman code for a distance alphabet symbol: always 5 bits,
+/// equal to the symbol itself.
+fn fixed_distance_code(symbol: u16) -> (u16, u8) {
+    (symbol, 5)
+}
+
+/// Length codes 257-285 each cover a range of match lengths, the shorter
+/// ranges encoded exactly and the longer ones needing extra bits to pick a
+/// length within the range. Returns `(range's first length, code, extra
+/// bits)` for the code covering `length`.
+fn length_code(length: u16) -> (u16, u16, u8) {
+    const TABLE: [(u16, u16, u8); 29] = [
+        (3, 257, 0),
+        (4, 258, 0),
+        (5, 259, 0),
+        (6, 260, 0),
+        (7, 261, 0),
+        (8, 262, 0),
+        (9, 263, 0),
+        (10, 264, 0),
+        (11, 265, 1),
+        (13, 266, 1),
+        (15, 267, 1),
+        (17, 268, 1),
+        (19, 269, 2),
+        (23, 270, 2),
+        (27, 271, 2),
+        (31, 272, 2),
+        (35, 273, 3),
+        (43, 274, 3),
+        (51, 275, 3),
+        (59, 276, 3),
+        (67, 277, 4),
+        (83, 278, 4),
+        (99, 279, 4),
+        (115, 280, 4),
+        (131, 281, 5),
+        (163, 282, 5),
+        (195, 283, 5),
+        (227, 284, 5),
+        (258, 285, 0),
+    ];
+    TABLE.iter().rev().find(|&&(base, _, _)| base <= length).copied().unwrap()
+}
+
+/// Distance codes 0-29 each cover a range of distances, the same
+/// shorter-ranges-first, extra-bits-for-longer-ranges scheme as
+/// [`length_code`]. Returns `(range's first distance, code, extra bits)` for
+/// the code covering `distance`.
+fn distance_code(distance: u16) -> (u16, u16, u8) {
+    const TABLE: [(u16, u16, u8); 30] = [
+        (1, 0, 0),
+        (2, 1, 0),
+        (3, 2, 0),
+        (4, 3, 0),
+        (5, 4, 1),
+        (7, 5, 1),
+        (9, 6, 2),
+        (13, 7, 2),
+        (17, 8, 3),
+        (25, 9, 3),
+        (33, 10, 4),
+        (49, 11, 4),
+        (65, 12, 5),
+        (97, 13, 5),
+        (129, 14, 6),
+        (193, 15, 6),
+        (257, 16, 7),
+        (385, 17, 7),
+        (513, 18, 8),
+        (769, 19, 8),
+        (1025, 20, 9),
+        (1537, 21, 9),
+        (2049, 22, 10),
+        (3073, 23, 10),
+        (4097, 24, 11),
+        (6145, 25, 11),
+        (8193, 26, 12),
+        (12289, 27, 12),
+        (16385, 28, 13),
+        (24577, 29, 13),
+    ];
+    TABLE.iter().rev().find(|&&(base, _, _)| base <= distance).copied().unwrap()
+}
+
+/// The Adler-32 checksum zlib streams are suffixed with.
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+/// The CRC-32 (ISO-HDLC) checksum PNG chunks are suffixed with.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_with_the_png_signature_and_ends_with_iend() {
+        let mut image = Image::new(2, 2);
+        let encoded = encode(&image);
+
+        assert_eq!(&encoded[..8], &SIGNATURE);
+        assert_eq!(&encoded[encoded.len() - 8..encoded.len() - 4], b"IEND");
+
+        image.buffer = vec![[1, 2, 3]; 4];
+        assert_eq!(encode(&image)[..8], SIGNATURE);
+    }
+
+    #[test]
+    fn ihdr_reports_dimensions_and_truecolor_color_type() {
+        let image = Image::new(7, 5);
+        let encoded = encode(&image);
+
+        // signature(8) + length(4) + "IHDR"(4) = 16 bytes before the payload.
+        let ihdr = &encoded[16..16 + 13];
+        assert_eq!(&ihdr[0..4], &7u32.to_be_bytes());
+        assert_eq!(&ihdr[4..8], &5u32.to_be_bytes());
+        assert_eq!(ihdr[8], 8); // bit depth
+        assert_eq!(ihdr[9], 2); // color type: truecolor RGB
+    }
+
+    #[test]
+    fn adler32_matches_known_value() {
+        // "Wikipedia" -> 0x11E60398, a commonly cited Adler-32 test vector.
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn crc32_matches_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}